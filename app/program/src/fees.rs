@@ -0,0 +1,276 @@
+//! # Fee Estimation
+//!
+//! The swap modules copy the user's outputs verbatim, so without an
+//! explicit check the program would happily sign a transaction that
+//! underpays or wildly overpays its fee. This module estimates the
+//! virtual size of the transaction the program is about to sign and
+//! verifies it affords the caller-declared fee rate, adding a change
+//! output for any surplus worth collecting.
+
+use arch_program::{msg, program_error::ProgramError};
+use bitcoin::{Amount, ScriptBuf, Transaction, TxOut};
+
+/// Outputs below this value are not worth their own fee to spend later, so
+/// leftover surplus under the threshold is left as extra fee instead of
+/// becoming a change output.
+const DUST_THRESHOLD_SATS: u64 = 546;
+
+/// Non-witness bytes every input contributes: outpoint (36) + empty
+/// scriptSig length prefix (1) + sequence (4).
+const INPUT_BASE_VBYTES: u64 = 41;
+
+/// Fixed per-transaction overhead: version (4) + input/output count varints
+/// (1 each, for typical small swap transactions) + lock_time (4).
+const TX_OVERHEAD_VBYTES: u64 = 10;
+
+/// The signature scheme an input will be satisfied with, used to estimate
+/// the witness weight it will carry once signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    /// A Taproot key-path spend: a single Schnorr signature, ~66 witness weight units
+    TaprootKeyPath,
+    /// A P2WPKH spend: signature + pubkey, ~108 witness weight units
+    P2wpkh,
+}
+
+impl InputKind {
+    /// Witness weight, in weight units, this kind of input is expected to add.
+    fn witness_weight_units(self) -> u64 {
+        match self {
+            InputKind::TaprootKeyPath => 66,
+            InputKind::P2wpkh => 108,
+        }
+    }
+
+    /// Guesses the input kind from the script it spends, defaulting to
+    /// P2WPKH for anything that isn't recognizably Taproot.
+    pub fn from_script(script: &ScriptBuf) -> Self {
+        if script.is_p2tr() {
+            InputKind::TaprootKeyPath
+        } else {
+            InputKind::P2wpkh
+        }
+    }
+}
+
+/// Size, in bytes, of a CompactSize-encoded integer.
+fn varint_size(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Serialized size of a single output: 8-byte value + script length prefix + script.
+fn output_vbytes(output: &TxOut) -> u64 {
+    let script_len = output.script_pubkey.len() as u64;
+    8 + varint_size(script_len) + script_len
+}
+
+/// Estimates the virtual size of `tx` once every input in `input_kinds` (in
+/// the same order as `tx.input`) carries its witness.
+pub fn estimate_vsize(tx: &Transaction, input_kinds: &[InputKind]) -> u64 {
+    let inputs_vsize: u64 = input_kinds
+        .iter()
+        .map(|kind| INPUT_BASE_VBYTES + kind.witness_weight_units().div_ceil(4))
+        .sum();
+
+    let outputs_vsize: u64 = tx.output.iter().map(output_vbytes).sum();
+
+    TX_OVERHEAD_VBYTES + inputs_vsize + outputs_vsize
+}
+
+/// Verifies that the inputs being spent cover `tx`'s outputs plus the fee
+/// implied by `fee_rate_sat_per_vb` at `tx`'s current size, returning the
+/// unspent surplus.
+///
+/// `input_amounts` and `input_kinds` must be given in the same order as
+/// `tx.input`. Returns `ProgramError::Custom(505)` if outputs exceed inputs,
+/// or `ProgramError::Custom(506)` if the surplus doesn't cover the fee.
+///
+/// This only accounts for `tx`'s outputs as they stand when called: a change
+/// output appended afterwards has its own bytes to pay for, which this does
+/// not know about. Callers that may add change should use
+/// [`apply_fee_and_change`] instead, which prices the change output in
+/// before it's added.
+fn verify_fee(
+    tx: &Transaction,
+    input_kinds: &[InputKind],
+    input_amounts: &[Amount],
+    fee_rate_sat_per_vb: u64,
+) -> Result<Amount, ProgramError> {
+    let vsize = estimate_vsize(tx, input_kinds);
+    let fee = Amount::from_sat(vsize * fee_rate_sat_per_vb);
+
+    let total_in: Amount = input_amounts.iter().copied().sum();
+    let total_out: Amount = tx.output.iter().map(|output| output.value).sum();
+
+    let surplus = total_in.checked_sub(total_out).ok_or_else(|| {
+        msg!("Swap transaction outputs exceed its inputs");
+        ProgramError::Custom(505)
+    })?;
+
+    if surplus < fee {
+        msg!(
+            "Swap transaction underpays fee: needs {} sat, has {} sat surplus",
+            fee.to_sat(),
+            surplus.to_sat()
+        );
+        return Err(ProgramError::Custom(506));
+    }
+
+    Ok(surplus - fee)
+}
+
+/// Verifies `tx`'s inputs cover its outputs plus the fee at `fee_rate_sat_per_vb`,
+/// then appends a change output paying the leftover surplus to `change_script`
+/// if that surplus clears the dust threshold once the change output's own
+/// bytes are paid for.
+///
+/// Pricing the change output's bytes into the fee before appending it (rather
+/// than appending it and hoping the already-verified fee still covers the
+/// now-larger transaction) is what keeps the final, signed transaction from
+/// underpaying `fee_rate_sat_per_vb` for its actual size.
+///
+/// `input_amounts` and `input_kinds` must be given in the same order as
+/// `tx.input`. Returns `ProgramError::Custom(505)` if outputs exceed inputs,
+/// or `ProgramError::Custom(506)` if the surplus doesn't cover the fee at
+/// `tx`'s pre-change size.
+pub fn apply_fee_and_change(
+    tx: &mut Transaction,
+    input_kinds: &[InputKind],
+    input_amounts: &[Amount],
+    fee_rate_sat_per_vb: u64,
+    change_script: ScriptBuf,
+) -> Result<(), ProgramError> {
+    let surplus = verify_fee(tx, input_kinds, input_amounts, fee_rate_sat_per_vb)?;
+
+    let change_output_vbytes = output_vbytes(&TxOut {
+        value: Amount::from_sat(0),
+        script_pubkey: change_script.clone(),
+    });
+    let change_fee = Amount::from_sat(change_output_vbytes * fee_rate_sat_per_vb);
+
+    if let Some(change_amount) = surplus.checked_sub(change_fee) {
+        if change_amount.to_sat() > DUST_THRESHOLD_SATS {
+            tx.output.push(TxOut {
+                value: change_amount,
+                script_pubkey: change_script,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, Sequence, TxIn, Txid, Witness};
+
+    fn tx_with_outputs(outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: 0,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: outputs,
+        }
+    }
+
+    fn script_of_len(len: usize) -> ScriptBuf {
+        ScriptBuf::from(vec![0u8; len])
+    }
+
+    #[test]
+    fn estimate_vsize_matches_hand_computed_case() {
+        let tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(500),
+            script_pubkey: script_of_len(34),
+        }]);
+
+        // overhead 10 + one Taproot input (41 base + ceil(66/4)=17) + one
+        // 34-byte output (8 value + 1 varint + 34 script) = 10 + 58 + 43
+        assert_eq!(estimate_vsize(&tx, &[InputKind::TaprootKeyPath]), 111);
+    }
+
+    #[test]
+    fn apply_fee_and_change_funds_the_change_outputs_own_bytes() {
+        let mut tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(500),
+            script_pubkey: script_of_len(34),
+        }]);
+
+        apply_fee_and_change(
+            &mut tx,
+            &[InputKind::TaprootKeyPath],
+            &[Amount::from_sat(2_000)],
+            1,
+            script_of_len(34),
+        )
+        .expect("fee should be affordable");
+
+        // surplus = 2000 - 500 = 1500; fee at pre-change size (111 vbytes) =
+        // 111, leaving 1389; the change output itself costs 43 more vbytes,
+        // leaving 1346 paid to change_script.
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[1].value, Amount::from_sat(1_346));
+
+        // The transaction actually pays `fee_rate_sat_per_vb` for its real,
+        // post-change size: total paid to miners == inputs - all outputs.
+        let total_out: Amount = tx.output.iter().map(|o| o.value).sum();
+        let fee_paid = Amount::from_sat(2_000) - total_out;
+        let actual_vsize = estimate_vsize(&tx, &[InputKind::TaprootKeyPath]);
+        assert_eq!(fee_paid.to_sat(), actual_vsize);
+    }
+
+    #[test]
+    fn apply_fee_and_change_skips_dust_change() {
+        let mut tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(500),
+            script_pubkey: script_of_len(34),
+        }]);
+
+        apply_fee_and_change(
+            &mut tx,
+            &[InputKind::TaprootKeyPath],
+            &[Amount::from_sat(650)],
+            1,
+            script_of_len(34),
+        )
+        .expect("fee should be affordable");
+
+        // surplus = 650 - 500 = 150, well under the fee-plus-dust threshold
+        // once the change output's own bytes are priced in, so no change.
+        assert_eq!(tx.output.len(), 1);
+    }
+
+    #[test]
+    fn apply_fee_and_change_rejects_underpaid_fee() {
+        let mut tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(500),
+            script_pubkey: script_of_len(34),
+        }]);
+
+        let result = apply_fee_and_change(
+            &mut tx,
+            &[InputKind::TaprootKeyPath],
+            &[Amount::from_sat(600)],
+            1,
+            script_of_len(34),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -1,20 +1,30 @@
 //! # CHIMERA Hybrid Swap Program
 //! 
 //! This program implements a trustless atomic swap between Bitcoin inscriptions and Runes on the Arch Network.
-//! It provides two main functionalities:
+//! It provides four main functionalities:
 //! 1. Swap an inscription for Runes
 //! 2. Swap Runes for an inscription
+//! 3. Refund an escrowed swap whose counterparty never completed, once the agreed timelock has passed
+//! 4. Batch any number of the above swaps into a single atomic transaction
 //!
 //! The program uses Bitcoin's PSBT (Partially Signed Bitcoin Transaction) format for secure transaction handling.
 
-use arch_program:{
+use arch_program::{
     account::AccountInfo, entrypoint, msg, program_error::ProgramError, pubkey::Pubkey,
 };
+use swap_batch::{swap_batch};
 use swap_inscription_rune::{swap_inscription_rune};
+use swap_refund::{swap_refund};
 use swap_rune_inscription::{swap_rune_inscription};
 
 // Import the swap modules
+pub mod fees;
+pub mod policy;
+pub mod psbt;
+pub mod runestone;
+pub mod swap_batch;
 pub mod swap_inscription_rune;
+pub mod swap_refund;
 pub mod swap_rune_inscription;
 
 /// The entrypoint of the program, registered with the Arch Network runtime.
@@ -46,6 +56,16 @@ pub fn process_instruction(
             msg!("Processing Swap Runes for Inscription");
             swap_rune_inscription(accounts, program_id, instruction_data)
         }
+        // Instruction 2: Refund a timed-out escrow
+        2 => {
+            msg!("Processing Swap Refund");
+            swap_refund(accounts, program_id, instruction_data)
+        }
+        // Instruction 3: Batch multiple swaps into one atomic transaction
+        3 => {
+            msg!("Processing Batch Swap");
+            swap_batch(accounts, program_id, instruction_data)
+        }
         // Invalid instruction code
         _ => {
             msg!("Invalid instruction code provided");
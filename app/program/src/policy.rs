@@ -0,0 +1,55 @@
+//! # Custody Policy
+//!
+//! The program's custody of inscription and Rune UTXOs was previously only
+//! implicit in which input index it chose to sign, which rules out
+//! multisig or timelocked custody. This module lets the program be
+//! configured with a miniscript descriptor (e.g. a `wsh(multi(...))`
+//! multisig or a `tr(...)` with a timeout branch), derive the escrow script
+//! it resolves to, and verify that a program-signed input actually spends a
+//! UTXO matching that descriptor before the program ever signs it.
+
+use std::str::FromStr;
+
+use arch_program::{msg, program_error::ProgramError};
+use bitcoin::{PublicKey, ScriptBuf};
+use miniscript::Descriptor;
+
+/// A parsed custody descriptor and the escrow script it resolves to.
+pub struct Policy {
+    script_pubkey: ScriptBuf,
+}
+
+impl Policy {
+    /// Parses a descriptor string (e.g. `wsh(multi(2,A,B,C))` or
+    /// `tr(A,{pk(B)})`) and derives the escrow script it controls.
+    pub fn parse(descriptor: &str) -> Result<Self, ProgramError> {
+        let descriptor = Descriptor::<PublicKey>::from_str(descriptor).map_err(|_e| {
+            msg!("Failed to parse policy descriptor");
+            ProgramError::InvalidArgument
+        })?;
+
+        descriptor.sanity_check().map_err(|_e| {
+            msg!("Policy descriptor failed sanity check");
+            ProgramError::InvalidArgument
+        })?;
+
+        Ok(Self {
+            script_pubkey: descriptor.script_pubkey(),
+        })
+    }
+
+    /// The escrow script pubkey this policy's UTXOs must use.
+    pub fn script_pubkey(&self) -> &ScriptBuf {
+        &self.script_pubkey
+    }
+
+    /// Verifies that `script` (the previous output script of a UTXO the
+    /// program is about to sign for) matches this policy's escrow script.
+    pub fn verify_script(&self, script: &ScriptBuf) -> Result<(), ProgramError> {
+        if script != &self.script_pubkey {
+            msg!("Input does not spend a UTXO matching the custody policy");
+            return Err(ProgramError::Custom(507));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! # PSBT Helpers
+//!
+//! Wraps the BIP-174 Updater/Signer roles the swap modules need: decoding
+//! the user-supplied PSBT without panicking on malformed, attacker-controlled
+//! bytes, and recovering each input's previous output (amount + script) from
+//! `witness_utxo` / `non_witness_utxo` rather than trusting a bare
+//! consensus-deserialized transaction that carries no prevout information.
+
+use arch_program::{msg, program_error::ProgramError};
+use bitcoin::{Psbt, TxOut};
+
+/// Decodes a BIP-174 PSBT, returning a `ProgramError` instead of panicking
+/// on malformed, attacker-controlled bytes.
+pub fn decode(psbt_bytes: &[u8]) -> Result<Psbt, ProgramError> {
+    Psbt::deserialize(psbt_bytes).map_err(|_e| {
+        msg!("Failed to parse user-supplied PSBT");
+        ProgramError::InvalidArgument
+    })
+}
+
+/// Recovers the previous output (amount + script) spent by
+/// `psbt.unsigned_tx.input[index]`, following the Updater role: prefer
+/// `witness_utxo`, falling back to indexing `non_witness_utxo` by the
+/// input's own `vout`.
+pub fn prevout(psbt: &Psbt, index: usize) -> Result<TxOut, ProgramError> {
+    let psbt_input = psbt.inputs.get(index).ok_or(ProgramError::InvalidArgument)?;
+
+    if let Some(witness_utxo) = &psbt_input.witness_utxo {
+        return Ok(witness_utxo.clone());
+    }
+
+    if let Some(non_witness_utxo) = &psbt_input.non_witness_utxo {
+        let vout = psbt
+            .unsigned_tx
+            .input
+            .get(index)
+            .ok_or(ProgramError::InvalidArgument)?
+            .previous_output
+            .vout as usize;
+
+        return non_witness_utxo
+            .output
+            .get(vout)
+            .cloned()
+            .ok_or(ProgramError::InvalidArgument);
+    }
+
+    msg!("PSBT input {} is missing witness_utxo/non_witness_utxo", index);
+    Err(ProgramError::InvalidArgument)
+}
+
+/// Recovers the previous output for every input in `psbt`, in order.
+pub fn prevouts(psbt: &Psbt) -> Result<Vec<TxOut>, ProgramError> {
+    (0..psbt.unsigned_tx.input.len())
+        .map(|index| prevout(psbt, index))
+        .collect()
+}
@@ -0,0 +1,340 @@
+//! # Runestone Decoding
+//!
+//! A Runestone is the protocol message that the Runes standard embeds in an
+//! `OP_RETURN` output to move rune balances between transaction outputs.
+//! This module decodes just enough of that envelope for the swap modules to
+//! confirm that a transaction actually carries the edict it claims to, and
+//! encodes a fresh Runestone when several swaps are batched into one
+//! transaction and their edicts need to be merged into a single envelope.
+
+use bitcoin::blockdata::opcodes::all::{OP_PUSHNUM_13, OP_RETURN};
+use bitcoin::blockdata::script::{Builder, PushBytesBuf};
+use bitcoin::script::Instruction;
+use bitcoin::{ScriptBuf, Transaction};
+
+use arch_program::{msg, program_error::ProgramError};
+
+/// `OP_RETURN OP_PUSHNUM_13`, the two-byte prefix that marks a Runestone output.
+const RUNESTONE_MAGIC: [u8; 2] = [0x6a, 0x5d];
+
+/// The tag that marks the start of the edict list within the varint stream.
+const TAG_BODY: u128 = 0;
+
+/// A single rune transfer instruction decoded from a Runestone's edict body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edict {
+    /// Block height of the rune being transferred
+    pub id_block: u64,
+
+    /// Transaction index within `id_block` of the rune being transferred
+    pub id_tx: u32,
+
+    /// Quantity of the rune being transferred
+    pub amount: u128,
+
+    /// Index into `tx.output` that receives `amount`
+    pub output: u32,
+}
+
+impl Edict {
+    /// The rune ID in `block:tx` form, matching how rune IDs are written elsewhere.
+    pub fn rune_id(&self) -> String {
+        format!("{}:{}", self.id_block, self.id_tx)
+    }
+}
+
+/// Decodes the Runestone carried by `tx`, if any.
+///
+/// Returns `None` when no output begins with the Runestone magic bytes, or
+/// when the payload doesn't contain a well-formed edict body.
+pub fn decode(tx: &Transaction) -> Option<Vec<Edict>> {
+    find(tx).map(|(_index, edicts)| edicts)
+}
+
+/// Locates the first Runestone output in `tx` and decodes its edicts.
+///
+/// Returns the output's index alongside its edicts so a caller merging
+/// several transactions can drop the original envelope and re-point the
+/// edicts at the merged output list.
+pub fn find(tx: &Transaction) -> Option<(usize, Vec<Edict>)> {
+    let index = tx.output.iter().position(is_runestone_output)?;
+    let payload = collect_push_data(&tx.output[index].script_pubkey);
+    let integers = decode_varints(&payload)?;
+    let body = body_after_tag(&integers)?;
+    Some((index, decode_edicts(body)))
+}
+
+fn is_runestone_output(output: &bitcoin::TxOut) -> bool {
+    let script = output.script_pubkey.as_bytes();
+    script.len() >= RUNESTONE_MAGIC.len() && script[..2] == RUNESTONE_MAGIC
+}
+
+/// Concatenates all of a Runestone script's data pushes into a single buffer.
+fn collect_push_data(script: &ScriptBuf) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for instruction in script.instructions() {
+        if let Ok(Instruction::PushBytes(push)) = instruction {
+            payload.extend_from_slice(push.as_bytes());
+        }
+    }
+    payload
+}
+
+/// Encodes `edicts` into a single Runestone `OP_RETURN` output script.
+///
+/// `edicts` must already be sorted by `(id_block, id_tx)` ascending, as the
+/// wire format only carries non-negative deltas between consecutive edicts.
+pub fn encode(edicts: &[Edict]) -> Result<ScriptBuf, ProgramError> {
+    let mut integers: Vec<u128> = vec![TAG_BODY];
+    let mut block = 0u64;
+    let mut tx_index = 0u32;
+
+    for edict in edicts {
+        let (id_block_delta, id_tx_delta) = if edict.id_block != block {
+            let delta = edict.id_block.checked_sub(block).ok_or_else(|| {
+                msg!("Edicts must be sorted by rune ID before encoding a Runestone");
+                ProgramError::InvalidArgument
+            })?;
+            (delta, edict.id_tx as u64)
+        } else {
+            let delta = edict.id_tx.checked_sub(tx_index).ok_or_else(|| {
+                msg!("Edicts must be sorted by rune ID before encoding a Runestone");
+                ProgramError::InvalidArgument
+            })?;
+            (0u64, delta as u64)
+        };
+
+        block = edict.id_block;
+        tx_index = edict.id_tx;
+
+        integers.push(id_block_delta as u128);
+        integers.push(id_tx_delta as u128);
+        integers.push(edict.amount);
+        integers.push(edict.output as u128);
+    }
+
+    let mut payload = Vec::new();
+    for integer in integers {
+        encode_varint(integer, &mut payload);
+    }
+
+    let push_bytes = PushBytesBuf::try_from(payload).map_err(|_e| {
+        msg!("Runestone payload too large to encode");
+        ProgramError::InvalidArgument
+    })?;
+
+    Ok(Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_opcode(OP_PUSHNUM_13)
+        .push_slice(&push_bytes)
+        .into_script())
+}
+
+/// Encodes `value` as a single LEB128 varint, appending it to `out`.
+fn encode_varint(mut value: u128, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Decodes `data` as a flat sequence of LEB128 varints.
+fn decode_varints(data: &[u8]) -> Option<Vec<u128>> {
+    let mut integers = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let mut value: u128 = 0;
+        let mut shift = 0u32;
+
+        loop {
+            let byte = *data.get(i)?;
+            i += 1;
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift >= 128 {
+                return None;
+            }
+        }
+
+        integers.push(value);
+    }
+
+    Some(integers)
+}
+
+/// Walks the tag/value pairs until tag `0` (Body) is found, returning the
+/// integers that follow it, which form the flat edict list.
+fn body_after_tag(integers: &[u128]) -> Option<&[u128]> {
+    let mut i = 0;
+    while i + 1 < integers.len() {
+        let tag = integers[i];
+        if tag == TAG_BODY {
+            return Some(&integers[i + 1..]);
+        }
+        i += 2;
+    }
+    None
+}
+
+/// Decodes the flat `(id_block_delta, id_tx_delta, amount, output)` body into
+/// `Edict`s, accumulating the delta-encoded rune ID across edicts.
+fn decode_edicts(body: &[u128]) -> Vec<Edict> {
+    let mut edicts = Vec::new();
+    let mut block = 0u64;
+    let mut tx_index = 0u32;
+
+    for fields in body.chunks(4) {
+        let [id_block_delta, id_tx_delta, amount, output] = fields else {
+            break;
+        };
+        let id_block_delta = *id_block_delta as u64;
+        let id_tx_delta = *id_tx_delta as u32;
+        let output = *output as u32;
+
+        if id_block_delta != 0 {
+            block += id_block_delta;
+            tx_index = id_tx_delta;
+        } else {
+            tx_index += id_tx_delta;
+        }
+
+        edicts.push(Edict {
+            id_block: block,
+            id_tx: tx_index,
+            amount: *amount,
+            output,
+        });
+    }
+
+    edicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, Sequence, TxIn, TxOut, Txid, Witness};
+
+    fn tx_with_outputs(outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: 0,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: outputs,
+        }
+    }
+
+    #[test]
+    fn decodes_a_known_good_runestone_byte_vector() {
+        // tag 0 (Body) followed by one edict: (id_block_delta=840000,
+        // id_tx_delta=3, amount=500, output=1), each LEB128-encoded.
+        let payload: [u8; 8] = [0x00, 0xc0, 0xa2, 0x33, 0x03, 0xf4, 0x03, 0x01];
+        let mut script_bytes = RUNESTONE_MAGIC.to_vec();
+        script_bytes.push(payload.len() as u8);
+        script_bytes.extend_from_slice(&payload);
+
+        let tx = tx_with_outputs(vec![
+            TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new(),
+            },
+            TxOut {
+                value: Amount::from_sat(0),
+                script_pubkey: ScriptBuf::from(script_bytes),
+            },
+        ]);
+
+        let edicts = decode(&tx).expect("expected a decodable Runestone");
+        assert_eq!(
+            edicts,
+            vec![Edict {
+                id_block: 840_000,
+                id_tx: 3,
+                amount: 500,
+                output: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn decode_returns_none_without_a_runestone_output() {
+        let tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: ScriptBuf::new(),
+        }]);
+
+        assert_eq!(decode(&tx), None);
+    }
+
+    #[test]
+    fn encode_then_decode_round_trips_multiple_edicts() {
+        let edicts = vec![
+            Edict {
+                id_block: 840_000,
+                id_tx: 1,
+                amount: 100,
+                output: 0,
+            },
+            Edict {
+                id_block: 840_000,
+                id_tx: 4,
+                amount: 200,
+                output: 1,
+            },
+            Edict {
+                id_block: 840_050,
+                id_tx: 0,
+                amount: 300,
+                output: 2,
+            },
+        ];
+
+        let script = encode(&edicts).expect("encoding should succeed");
+        let tx = tx_with_outputs(vec![TxOut {
+            value: Amount::from_sat(0),
+            script_pubkey: script,
+        }]);
+
+        assert_eq!(decode(&tx), Some(edicts));
+    }
+
+    #[test]
+    fn encode_rejects_edicts_not_sorted_by_rune_id() {
+        let edicts = vec![
+            Edict {
+                id_block: 840_050,
+                id_tx: 0,
+                amount: 300,
+                output: 0,
+            },
+            Edict {
+                id_block: 840_000,
+                id_tx: 1,
+                amount: 100,
+                output: 1,
+            },
+        ];
+
+        assert!(encode(&edicts).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! # Batched Swap Module
+//!
+//! Market-maker clients often want to settle many swaps in one on-chain
+//! transaction and one runtime signing round-trip instead of one instruction
+//! per swap. This module folds any number of inscription<->Rune swap ops
+//! into a single transaction: each op's inputs/outputs are appended in
+//! order and its signing indices are offset to land on the combined
+//! transaction, so the whole batch commits atomically or not at all.
+//!
+//! Every rune-bearing op carries its own Runestone `OP_RETURN` output, but a
+//! transaction with more than one `OP_RETURN` is non-standard and is treated
+//! as a cenotaph (burning the runes) by the Runes protocol. So rather than
+//! appending each op's outputs verbatim, each op's Runestone is stripped out
+//! of its own output list and its edicts are re-pointed at the combined
+//! transaction's output indices; once every op has folded in, the edicts are
+//! merged into a single Runestone appended to the end of `tx.output`.
+
+use arch_program::{
+    account::AccountInfo,
+    input_to_sign::InputToSign,
+    msg,
+    program::next_account_info,
+    program::set_transaction_to_sign,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    transaction_to_sign::TransactionToSign,
+};
+use bitcoin::{self, absolute::LockTime, transaction::Version, Amount, Transaction, TxOut};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::runestone::{self, Edict};
+use crate::swap_inscription_rune::{self, SwapInscriptionRuneParams};
+use crate::swap_rune_inscription::{self, SwapRuneInscriptionParams};
+
+/// Executes a batch of inscription<->Rune swaps as a single transaction.
+///
+/// # Arguments
+/// * `accounts` - A slice of account information. Should contain exactly one account (the program's account).
+/// * `_program_id` - The public key of the program (unused in this function).
+/// * `instruction_data` - Serialized `Vec<SwapOp>` containing the batched swaps.
+///
+/// # Returns
+/// * `Result<(), ProgramError>` - Returns `Ok(())` on success, or a `ProgramError` on failure.
+pub(crate) fn swap_batch(
+    accounts: &[AccountInfo],
+    _program_id: &Pubkey,
+    instruction_data: &[u8],
+) -> Result<(), ProgramError> {
+    // Verify exactly one account is provided (the program's account)
+    if accounts.len() != 1 {
+        return Err(ProgramError::Custom(501));
+    }
+
+    let account_iter = &mut accounts.iter();
+    let account = next_account_info(account_iter)?;
+
+    // Deserialize the instruction data into a list of swap ops
+    let ops: Vec<SwapOp> =
+        borsh::from_slice(&instruction_data[1..]).map_err(|_e| ProgramError::InvalidArgument)?;
+
+    // Create a new transaction with version 2 and no lock time
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: vec![],
+    };
+
+    let mut inputs_to_sign = Vec::new();
+    let mut combined_edicts: Vec<Edict> = Vec::new();
+
+    // Fold each op's inputs/outputs into the combined transaction, offsetting
+    // its signing indices to land where its inputs end up in `tx`
+    for op in &ops {
+        let input_offset = tx.input.len() as u32;
+
+        let (mut op_tx, relative_signing_indices) = match op {
+            SwapOp::InscriptionForRune(params) => swap_inscription_rune::build_swap(params)?,
+            SwapOp::RuneForInscription(params) => swap_rune_inscription::build_swap(params)?,
+        };
+
+        // Strip this op's own Runestone out of its output list, and re-point
+        // its edicts at the combined transaction's output indices instead of
+        // the op's own, now-discarded, local output list
+        if let Some((runestone_index, edicts)) = runestone::find(&op_tx) {
+            op_tx.output.remove(runestone_index);
+
+            let output_offset = tx.output.len() as u32;
+            combined_edicts.extend(edicts.into_iter().map(|edict| {
+                let local_output = if edict.output > runestone_index as u32 {
+                    edict.output - 1
+                } else {
+                    edict.output
+                };
+                Edict {
+                    output: output_offset + local_output,
+                    ..edict
+                }
+            }));
+        }
+
+        tx.input.extend(op_tx.input);
+        tx.output.extend(op_tx.output);
+
+        inputs_to_sign.extend(relative_signing_indices.into_iter().map(|index| InputToSign {
+            index: input_offset + index,
+            signer: account.key.clone(),
+        }));
+    }
+
+    // Merge every op's edicts into a single Runestone, so the batched
+    // transaction carries at most one `OP_RETURN` output
+    if !combined_edicts.is_empty() {
+        combined_edicts.sort_by_key(|edict| (edict.id_block, edict.id_tx));
+        let runestone_script = runestone::encode(&combined_edicts)?;
+        tx.output.push(TxOut {
+            value: Amount::from_sat(0),
+            script_pubkey: runestone_script,
+        });
+    }
+
+    msg!("Constructed batch transaction: {:?}", tx);
+
+    // Prepare the combined transaction for signing
+    let tx_to_sign = TransactionToSign {
+        tx_bytes: &bitcoin::consensus::serialize(&tx),
+        inputs_to_sign: &inputs_to_sign,
+    };
+
+    msg!("Batch transaction ready for signing: {:?}", tx_to_sign);
+
+    // Forward the transaction to the Arch Network runtime for signing and broadcasting
+    set_transaction_to_sign(accounts, tx_to_sign)
+}
+
+/// A single swap within a batch, carrying the same params its standalone
+/// instruction would take.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub enum SwapOp {
+    /// Swap an inscription for Runes
+    InscriptionForRune(SwapInscriptionRuneParams),
+    /// Swap Runes for an inscription
+    RuneForInscription(SwapRuneInscriptionParams),
+}
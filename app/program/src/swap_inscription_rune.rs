@@ -7,25 +7,30 @@
 use std::str::FromStr;
 
 use arch_program::{
-    account::AccountInfo, 
-    input_to_sign::InputToSign, 
-    msg, 
+    account::AccountInfo,
+    input_to_sign::InputToSign,
+    msg,
     program::next_account_info,
-    program::set_transaction_to_sign, 
-    program_error::ProgramError, 
+    program::set_transaction_to_sign,
+    program_error::ProgramError,
     pubkey::Pubkey,
     transaction_to_sign::TransactionToSign,
 };
+use crate::fees;
+use crate::policy::Policy;
+use crate::psbt;
+use crate::runestone;
 use bitcoin::{
-    self, 
-    absolute::LockTime, 
-    transaction::Version, 
-    OutPoint, 
-    ScriptBuf, 
-    Sequence, 
+    self,
+    absolute::LockTime,
+    transaction::Version,
+    Amount,
+    OutPoint,
+    ScriptBuf,
+    Sequence,
     Transaction,
-    TxIn, 
-    Txid, 
+    TxIn,
+    Txid,
     Witness,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -60,13 +65,54 @@ pub(crate) fn swap_inscription_rune(
     // Deserialize the instruction data into SwapInscriptionRuneParams
     let params: SwapInscriptionRuneParams =
         borsh::from_slice(&instruction_data[1..]).map_err(|_e| ProgramError::InvalidArgument)?;
-    
-    // Deserialize the user's PSBT into a Bitcoin transaction
-    let user_swap_tx: Transaction =
-        bitcoin::consensus::deserialize(&params.user_swap_psbt).unwrap();
+
+    let (tx, relative_signing_indices) = build_swap(&params)?;
+
+    let inputs_to_sign: Vec<InputToSign> = relative_signing_indices
+        .into_iter()
+        .map(|index| InputToSign {
+            index,
+            signer: account.key.clone(),
+        })
+        .collect();
+
+    let tx_to_sign = TransactionToSign {
+        tx_bytes: &bitcoin::consensus::serialize(&tx),
+        inputs_to_sign: &inputs_to_sign,
+    };
+
+    msg!("Transaction ready for signing: {:?}", tx_to_sign);
+
+    // Forward the transaction to the Arch Network runtime for signing and broadcasting
+    set_transaction_to_sign(accounts, tx_to_sign)
+}
+
+/// Builds and verifies the inscription-for-Rune swap transaction described by
+/// `params`, without signing or broadcasting it.
+///
+/// Returns the constructed transaction together with the indices, relative
+/// to that transaction's own inputs, that the program must sign. Shared by
+/// the single-swap entrypoint above and the batch instruction, which offsets
+/// these indices into a combined, multi-op transaction.
+pub(crate) fn build_swap(
+    params: &SwapInscriptionRuneParams,
+) -> Result<(Transaction, Vec<u32>), ProgramError> {
+    // Decode the user's PSBT and pull out its unsigned transaction
+    let user_psbt = psbt::decode(&params.user_swap_psbt)?;
+    let user_swap_tx = user_psbt.unsigned_tx.clone();
 
     msg!("Processing user's swap transaction: {:?}", user_swap_tx);
 
+    // If a custody policy is configured, the inscription UTXO must actually
+    // be held under that descriptor before the program signs for it
+    if let Some(descriptor) = &params.policy_descriptor {
+        let escrow_script = params.inscription_script.as_ref().ok_or_else(|| {
+            msg!("policy_descriptor set without inscription_script to verify");
+            ProgramError::InvalidArgument
+        })?;
+        Policy::parse(descriptor)?.verify_script(&ScriptBuf::from(escrow_script.clone()))?;
+    }
+
     // Create a new transaction with version 2 and no lock time
     let mut tx = Transaction {
         version: Version::TWO,
@@ -78,7 +124,10 @@ pub(crate) fn swap_inscription_rune(
     // Add the inscription UTXO as the first input
     tx.input.push(TxIn {
         previous_output: OutPoint {
-            txid: Txid::from_str(&params.inscription_txid).unwrap(),
+            txid: Txid::from_str(&params.inscription_txid).map_err(|_| {
+                msg!("Invalid inscription txid format: {}", params.inscription_txid);
+                ProgramError::InvalidArgument
+            })?,
             vout: params.inscription_vout as u32,
         },
         script_sig: ScriptBuf::new(),  // Will be filled during signing
@@ -98,33 +147,131 @@ pub(crate) fn swap_inscription_rune(
 
     msg!("Constructed transaction: {:?}", tx);
 
-    // Prepare the transaction for signing
+    // Verify the inputs cover the outputs plus the agreed fee rate, and
+    // collect any worthwhile surplus into a program-controlled change output
+    let user_prevouts = psbt::prevouts(&user_psbt)?;
+    let input_amounts: Vec<Amount> = std::iter::once(Amount::from_sat(params.inscription_amount))
+        .chain(user_prevouts.iter().map(|prevout| prevout.value))
+        .collect();
+    let input_kinds: Vec<fees::InputKind> = std::iter::once(fees::InputKind::TaprootKeyPath)
+        .chain(
+            user_prevouts
+                .iter()
+                .map(|prevout| fees::InputKind::from_script(&prevout.script_pubkey)),
+        )
+        .collect();
+    fees::apply_fee_and_change(
+        &mut tx,
+        &input_kinds,
+        &input_amounts,
+        params.fee_rate,
+        ScriptBuf::from(params.change_script.clone()),
+    )?;
+
+    // The user only gets to spend the inscription input if the transaction
+    // they built actually pays the program back the agreed counter-asset.
+    verify_counter_asset_output(&tx, params)?;
+
     // Only the first input (the inscription) needs to be signed by the program
-    let tx_to_sign = TransactionToSign {
-        tx_bytes: &bitcoin::consensus::serialize(&tx),
-        inputs_to_sign: &[InputToSign {
-            index: 0,  // Only sign the first input (the inscription)
-            signer: account.key.clone(),
-        }],
-    };
+    Ok((tx, vec![0]))
+}
 
-    msg!("Transaction ready for signing: {:?}", tx_to_sign);
+/// Verifies that the constructed transaction actually pays the program the
+/// counter-asset it agreed to hand the inscription over for.
+///
+/// Returns `ProgramError::Custom(502)` when no output pays at least
+/// `expected_amount` to `expected_script`, which stops the program from
+/// signing away the inscription for free.
+fn verify_counter_asset_output(
+    tx: &Transaction,
+    params: &SwapInscriptionRuneParams,
+) -> Result<(), ProgramError> {
+    let expected_script = ScriptBuf::from(params.expected_script.clone());
+    let expected_amount = Amount::from_sat(params.expected_amount);
 
-    // Forward the transaction to the Arch Network runtime for signing and broadcasting
-    set_transaction_to_sign(accounts, tx_to_sign)
+    let is_paid = tx.output.iter().any(|output| {
+        output.script_pubkey == expected_script && output.value >= expected_amount
+    });
+
+    if !is_paid {
+        msg!("Swap transaction does not pay the expected counter-asset output");
+        return Err(ProgramError::Custom(502));
+    }
+
+    if let Some(expected_rune_id) = &params.expected_rune_id {
+        let expected_quantity = params.expected_rune_quantity.ok_or_else(|| {
+            msg!("expected_rune_id set without expected_rune_quantity");
+            ProgramError::InvalidArgument
+        })?;
+        let edicts = runestone::decode(tx).ok_or_else(|| {
+            msg!("Swap transaction does not carry a Runestone");
+            ProgramError::Custom(503)
+        })?;
+
+        let edict_matches = edicts.iter().any(|edict| {
+            &edict.rune_id() == expected_rune_id
+                && edict.amount >= expected_quantity
+                && edict.output == params.expected_rune_output_vout
+        });
+
+        if !edict_matches {
+            msg!("Runestone does not carry the expected rune edict");
+            return Err(ProgramError::Custom(504));
+        }
+    }
+
+    Ok(())
 }
 
 /// Parameters required for the inscription to rune swap.
-/// 
+///
 /// This struct is serialized/deserialized using Borsh for secure cross-program invocation.
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct SwapInscriptionRuneParams {
     /// The transaction ID of the inscription UTXO being spent
     pub inscription_txid: String,
-    
+
     /// The output index of the inscription UTXO being spent
     pub inscription_vout: u8,
-    
+
     /// The user's PSBT containing additional inputs/outputs for the swap
     pub user_swap_psbt: Vec<u8>,
+
+    /// Script pubkey of the program-controlled output that must receive the
+    /// agreed counter-asset before the inscription input is signed
+    pub expected_script: Vec<u8>,
+
+    /// Minimum amount, in satoshis, that `expected_script` must receive
+    pub expected_amount: u64,
+
+    /// Rune ID (`block:tx`) the program expects to receive, if the
+    /// counter-asset is a Rune rather than plain sats
+    pub expected_rune_id: Option<String>,
+
+    /// Minimum Rune quantity the Runestone edict must transfer to output
+    /// `expected_rune_output_vout`. Required (rejected with `InvalidArgument`
+    /// otherwise) whenever `expected_rune_id` is set, so a caller can't
+    /// silently accept a zero-amount edict.
+    pub expected_rune_quantity: Option<u128>,
+
+    /// Output index the Runestone edict must target when `expected_rune_id` is set
+    pub expected_rune_output_vout: u32,
+
+    /// Amount, in satoshis, of the inscription UTXO being spent
+    pub inscription_amount: u64,
+
+    /// Fee rate, in satoshis per virtual byte, the transaction must pay
+    pub fee_rate: u64,
+
+    /// Script pubkey that collects any fee-paying surplus above the dust threshold
+    pub change_script: Vec<u8>,
+
+    /// Miniscript descriptor (e.g. `wsh(multi(...))`, `tr(...)`) the
+    /// inscription UTXO must be custodied under, if the program is
+    /// configured for threshold/custodial escrow rather than sole control
+    pub policy_descriptor: Option<String>,
+
+    /// Script pubkey of the inscription UTXO being spent, required when
+    /// `policy_descriptor` is set so it can be checked against the descriptor
+    pub inscription_script: Option<Vec<u8>>,
 }
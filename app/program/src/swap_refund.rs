@@ -0,0 +1,158 @@
+//! # Timelocked Refund Module
+//!
+//! Escrowed inscription/Rune UTXOs are spendable either by the normal
+//! 2-party swap path, or by the original owner after a relative timelock
+//! elapses. This module builds the latter: a transaction that reclaims the
+//! escrow output once its `OP_CSV` delta has passed, so a swap that never
+//! completes doesn't strand the owner's funds forever.
+
+use std::str::FromStr;
+
+use arch_program::{
+    account::AccountInfo,
+    input_to_sign::InputToSign,
+    msg,
+    program::next_account_info,
+    program::set_transaction_to_sign,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    transaction_to_sign::TransactionToSign,
+};
+use bitcoin::{
+    self,
+    absolute::LockTime,
+    transaction::Version,
+    Amount,
+    OutPoint,
+    ScriptBuf,
+    Sequence,
+    Transaction,
+    TxIn,
+    TxOut,
+    Txid,
+    Witness,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::policy::Policy;
+
+/// Executes the refund of a timed-out escrow UTXO.
+///
+/// This function constructs a Bitcoin transaction that:
+/// 1. Spends the escrowed inscription/Rune UTXO with `Sequence` set to the
+///    agreed `OP_CSV` delta, so it is only valid once that many blocks have
+///    passed since the escrow output confirmed
+/// 2. Pays the reclaimed value to the original owner's destination
+/// 3. Signs the refund input with the program's private key
+///
+/// # Arguments
+/// * `accounts` - A slice of account information. Should contain exactly one account (the program's account).
+/// * `_program_id` - The public key of the program (unused in this function).
+/// * `instruction_data` - Serialized `RefundParams` containing the refund details.
+///
+/// # Returns
+/// * `Result<(), ProgramError>` - Returns `Ok(())` on success, or a `ProgramError` on failure.
+pub(crate) fn swap_refund(
+    accounts: &[AccountInfo],
+    _program_id: &Pubkey,
+    instruction_data: &[u8],
+) -> Result<(), ProgramError> {
+    // Verify exactly one account is provided (the program's account)
+    if accounts.len() != 1 {
+        return Err(ProgramError::Custom(501));
+    }
+
+    let account_iter = &mut accounts.iter();
+    let account = next_account_info(account_iter)?;
+
+    // Deserialize the instruction data into RefundParams
+    let params: RefundParams =
+        borsh::from_slice(&instruction_data[1..]).map_err(|_e| ProgramError::InvalidArgument)?;
+
+    // If a custody policy is configured, the escrow UTXO must actually be
+    // held under that descriptor before the program signs the refund
+    if let Some(descriptor) = &params.policy_descriptor {
+        let escrow_script = params.escrow_script.as_ref().ok_or_else(|| {
+            msg!("policy_descriptor set without escrow_script to verify");
+            ProgramError::InvalidArgument
+        })?;
+        Policy::parse(descriptor)?.verify_script(&ScriptBuf::from(escrow_script.clone()))?;
+    }
+
+    // Create a new transaction with version 2 and no lock time; the
+    // timelock is enforced by the input's relative Sequence, not nLockTime
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: vec![],
+    };
+
+    // Spend the escrowed UTXO, only valid after the agreed CSV delta
+    tx.input.push(TxIn {
+        previous_output: OutPoint {
+            txid: Txid::from_str(&params.escrow_txid).map_err(|_| {
+                msg!("Invalid escrow txid format: {}", params.escrow_txid);
+                ProgramError::InvalidArgument
+            })?,
+            vout: params.escrow_vout as u32,
+        },
+        script_sig: ScriptBuf::new(),                     // Will be filled during signing
+        sequence: Sequence::from_height(params.timelock_delta), // Enforces BIP68 relative locktime
+        witness: Witness::new(),                          // Will be filled during signing
+    });
+
+    // Pay the reclaimed value back to the original owner
+    tx.output.push(TxOut {
+        value: Amount::from_sat(params.refund_amount),
+        script_pubkey: ScriptBuf::from(params.refund_destination.clone()),
+    });
+
+    msg!("Constructed refund transaction: {:?}", tx);
+
+    // Prepare the transaction for signing
+    // Only the escrow input (index 0) needs to be signed by the program
+    let tx_to_sign = TransactionToSign {
+        tx_bytes: &bitcoin::consensus::serialize(&tx),
+        inputs_to_sign: &[InputToSign {
+            index: 0,
+            signer: account.key.clone(),
+        }],
+    };
+
+    msg!("Refund transaction ready for signing: {:?}", tx_to_sign);
+
+    // Forward the transaction to the Arch Network runtime for signing and broadcasting
+    set_transaction_to_sign(accounts, tx_to_sign)
+}
+
+/// Parameters required to reclaim a timed-out escrow UTXO.
+///
+/// This struct is serialized/deserialized using Borsh for secure cross-program invocation.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct RefundParams {
+    /// The transaction ID of the escrowed UTXO being reclaimed
+    pub escrow_txid: String,
+
+    /// The output index of the escrowed UTXO being reclaimed
+    pub escrow_vout: u8,
+
+    /// Relative timelock, in blocks, that must have elapsed since the escrow
+    /// output confirmed before the refund path is spendable (the `OP_CSV` delta)
+    pub timelock_delta: u16,
+
+    /// Script pubkey that reclaims the escrowed value
+    pub refund_destination: Vec<u8>,
+
+    /// Amount, in satoshis, being reclaimed
+    pub refund_amount: u64,
+
+    /// Miniscript descriptor (e.g. `wsh(multi(...))`, `tr(...)`) the escrow
+    /// UTXO must be custodied under, if the program is configured for
+    /// threshold/custodial escrow rather than sole control
+    pub policy_descriptor: Option<String>,
+
+    /// Script pubkey of the escrow UTXO being reclaimed, required when
+    /// `policy_descriptor` is set so it can be checked against the descriptor
+    pub escrow_script: Option<Vec<u8>>,
+}
@@ -18,17 +18,22 @@ use arch_program::{
     pubkey::Pubkey,
     transaction_to_sign::TransactionToSign,
 };
+use crate::fees;
+use crate::policy::Policy;
+use crate::psbt;
+use crate::runestone;
 use bitcoin::{
-    self, 
-    Transaction, 
-    transaction::Version, 
-    absolute::LockTime, 
-    OutPoint, 
-    TxIn, 
-    Txid, 
-    ScriptBuf, 
-    Sequence, 
-    Witness 
+    self,
+    Amount,
+    Transaction,
+    transaction::Version,
+    absolute::LockTime,
+    OutPoint,
+    TxIn,
+    Txid,
+    ScriptBuf,
+    Sequence,
+    Witness
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -55,18 +60,49 @@ pub fn swap_rune_inscription(
     if accounts.len() != 1 {
         return Err(ProgramError::Custom(501));
     }
-    
+
     let account_iter = &mut accounts.iter();
     let account = next_account_info(account_iter)?;
 
     // Deserialize the instruction data into SwapRuneInscriptionParams
     let params: SwapRuneInscriptionParams =
         borsh::from_slice(&instruction_data[1..]).map_err(|_e| ProgramError::InvalidArgument)?;
-    
-    // Deserialize the user's PSBT into a Bitcoin transaction
-    let user_swap_tx: Transaction =
-        bitcoin::consensus::deserialize(&params.user_swap_psbt).unwrap();
-        
+
+    let (tx, relative_signing_indices) = build_swap(&params)?;
+
+    let inputs_to_sign: Vec<InputToSign> = relative_signing_indices
+        .into_iter()
+        .map(|index| InputToSign {
+            index,
+            signer: account.key.clone(),
+        })
+        .collect();
+
+    let tx_to_sign = TransactionToSign {
+        tx_bytes: &bitcoin::consensus::serialize(&tx),
+        inputs_to_sign: &inputs_to_sign,
+    };
+
+    msg!("Transaction ready for signing: {:?}", tx_to_sign);
+
+    // Forward the transaction to the Arch Network runtime for signing and broadcasting
+    set_transaction_to_sign(accounts, tx_to_sign)
+}
+
+/// Builds and verifies the Rune-for-inscription swap transaction described by
+/// `params`, without signing or broadcasting it.
+///
+/// Returns the constructed transaction together with the indices, relative
+/// to that transaction's own inputs, that the program must sign. Shared by
+/// the single-swap entrypoint above and the batch instruction, which offsets
+/// these indices into a combined, multi-op transaction.
+pub(crate) fn build_swap(
+    params: &SwapRuneInscriptionParams,
+) -> Result<(Transaction, Vec<u32>), ProgramError> {
+    // Decode the user's PSBT and pull out its unsigned transaction
+    let user_psbt = psbt::decode(&params.user_swap_psbt)?;
+    let user_swap_tx = user_psbt.unsigned_tx.clone();
+
     msg!("Processing user's swap transaction: {:?}", user_swap_tx);
 
     // Create a new transaction with version 2 and no lock time
@@ -76,28 +112,44 @@ pub fn swap_rune_inscription(
         input: vec![],
         output: vec![],
     };
-    
-    // Track the number of inputs from the user's PSBT
-    let mut user_input_count = 0;
-    
+
+    // The inputs the program adds come after the PSBT's own inputs, per the
+    // PSBT's own input ordering, so they start at this index
+    let user_input_count = user_psbt.unsigned_tx.input.len() as u32;
+
     // Add all inputs from the user's PSBT first
     for input in &user_swap_tx.input {
         tx.input.push(input.clone());
-        user_input_count += 1;
     }
 
     // Add all outputs from the user's PSBT
     for output in &user_swap_tx.output {
         tx.output.push(output.clone());
     }
-    
+
+    // If a custody policy is configured, every Rune UTXO must actually be
+    // held under that descriptor before the program signs for it
+    let policy = params
+        .policy_descriptor
+        .as_ref()
+        .map(|descriptor| Policy::parse(descriptor))
+        .transpose()?;
+
     // Add each Rune UTXO as an input to the transaction
     for (i, txid) in params.rune_txids.iter().enumerate() {
         let vout = params.rune_vouts.get(i).ok_or_else(|| {
             msg!("Mismatch between rune_txids and rune_vouts");
             ProgramError::InvalidArgument
         })?;
-        
+
+        if let Some(policy) = &policy {
+            let escrow_script = params.rune_scripts.get(i).ok_or_else(|| {
+                msg!("policy_descriptor set without a rune_scripts entry for input {}", i);
+                ProgramError::InvalidArgument
+            })?;
+            policy.verify_script(&ScriptBuf::from(escrow_script.clone()))?;
+        }
+
         tx.input.push(TxIn {
             previous_output: OutPoint {
                 txid: Txid::from_str(txid).map_err(|_| {
@@ -114,38 +166,124 @@ pub fn swap_rune_inscription(
 
     msg!("Constructed transaction: {:?}", tx);
 
-    // Prepare the inputs that need to be signed by the program
+    // Verify the inputs cover the outputs plus the agreed fee rate, and
+    // collect any worthwhile surplus into a program-controlled change output
+    let user_prevouts = psbt::prevouts(&user_psbt)?;
+    let input_amounts: Vec<Amount> = user_prevouts
+        .iter()
+        .map(|prevout| prevout.value)
+        .chain(params.rune_amounts.iter().map(|amount| Amount::from_sat(*amount)))
+        .collect();
+    let input_kinds: Vec<fees::InputKind> = user_prevouts
+        .iter()
+        .map(|prevout| fees::InputKind::from_script(&prevout.script_pubkey))
+        .chain(params.rune_txids.iter().map(|_| fees::InputKind::TaprootKeyPath))
+        .collect();
+    fees::apply_fee_and_change(
+        &mut tx,
+        &input_kinds,
+        &input_amounts,
+        params.fee_rate,
+        ScriptBuf::from(params.change_script.clone()),
+    )?;
+
+    // The user only gets the Rune inputs signed over if the transaction they
+    // built actually pays the inscription back to them.
+    verify_counter_asset_output(&tx, params)?;
+
     // These are the Rune UTXOs that were added after the user's inputs
-    let mut inputs_to_sign = Vec::new();
-    for (i, _) in params.rune_txids.iter().enumerate() {
-        inputs_to_sign.push(InputToSign {
-            index: user_input_count + i as u32,  // Index of the Rune input to sign
-            signer: account.key.clone(),
-        });
+    let relative_signing_indices: Vec<u32> = (0..params.rune_txids.len() as u32)
+        .map(|i| user_input_count + i)
+        .collect();
+
+    Ok((tx, relative_signing_indices))
+}
+
+/// Verifies that the constructed transaction actually pays the user back the
+/// counter-asset (the inscription) they agreed to receive for their Runes.
+///
+/// Returns `ProgramError::Custom(502)` when no output pays at least
+/// `expected_amount` to `expected_script`, which stops the program from
+/// signing away the Rune inputs for free.
+fn verify_counter_asset_output(
+    tx: &Transaction,
+    params: &SwapRuneInscriptionParams,
+) -> Result<(), ProgramError> {
+    let expected_script = ScriptBuf::from(params.expected_script.clone());
+    let expected_amount = Amount::from_sat(params.expected_amount);
+
+    let is_paid = tx.output.iter().any(|output| {
+        output.script_pubkey == expected_script && output.value >= expected_amount
+    });
+
+    if !is_paid {
+        msg!("Swap transaction does not pay the expected counter-asset output");
+        return Err(ProgramError::Custom(502));
     }
 
-    let tx_to_sign = TransactionToSign {
-        tx_bytes: &bitcoin::consensus::serialize(&tx),
-        inputs_to_sign: &inputs_to_sign,
-    };
+    let edicts = runestone::decode(tx).ok_or_else(|| {
+        msg!("Swap transaction does not carry a Runestone");
+        ProgramError::Custom(503)
+    })?;
 
-    msg!("Transaction ready for signing: {:?}", tx_to_sign);
+    let edict_matches = edicts.iter().any(|edict| {
+        edict.rune_id() == params.expected_rune_id
+            && edict.amount >= params.expected_rune_amount
+            && edict.output == params.expected_rune_output_vout
+    });
 
-    // Forward the transaction to the Arch Network runtime for signing and broadcasting
-    set_transaction_to_sign(accounts, tx_to_sign)
+    if !edict_matches {
+        msg!("Runestone does not carry the expected rune edict");
+        return Err(ProgramError::Custom(504));
+    }
+
+    Ok(())
 }
 
 /// Parameters required for the rune to inscription swap.
-/// 
+///
 /// This struct is serialized/deserialized using Borsh for secure cross-program invocation.
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct SwapRuneInscriptionParams {
     /// List of transaction IDs containing the Rune UTXOs to be spent
     pub rune_txids: Vec<String>,
-    
+
     /// List of output indices corresponding to the Rune UTXOs
     pub rune_vouts: Vec<u8>,
-    
+
     /// The user's PSBT containing additional inputs/outputs for the swap
-    pub user_swap_psbt: Vec<u8>
+    pub user_swap_psbt: Vec<u8>,
+
+    /// Script pubkey of the output that must receive the inscription back
+    pub expected_script: Vec<u8>,
+
+    /// Minimum amount, in satoshis, that `expected_script` must receive
+    pub expected_amount: u64,
+
+    /// Rune ID (`block:tx`) the seller expects the spent Rune UTXOs to carry
+    pub expected_rune_id: String,
+
+    /// Minimum Rune quantity the Runestone edict must transfer
+    pub expected_rune_amount: u128,
+
+    /// Output index the Runestone edict must target
+    pub expected_rune_output_vout: u32,
+
+    /// Amounts, in satoshis, of each Rune UTXO in `rune_txids`/`rune_vouts`
+    pub rune_amounts: Vec<u64>,
+
+    /// Fee rate, in satoshis per virtual byte, the transaction must pay
+    pub fee_rate: u64,
+
+    /// Script pubkey that collects any fee-paying surplus above the dust threshold
+    pub change_script: Vec<u8>,
+
+    /// Miniscript descriptor (e.g. `wsh(multi(...))`, `tr(...)`) the Rune
+    /// UTXOs must be custodied under, if the program is configured for
+    /// threshold/custodial escrow rather than sole control
+    pub policy_descriptor: Option<String>,
+
+    /// Script pubkey of each Rune UTXO in `rune_txids`/`rune_vouts`, required
+    /// when `policy_descriptor` is set so each can be checked against it
+    pub rune_scripts: Vec<Vec<u8>>,
 }
\ No newline at end of file